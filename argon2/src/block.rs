@@ -0,0 +1,213 @@
+//! Block-level operations: the `G` compression function used by
+//! `fill_segment` to derive each new memory block from its predecessor and
+//! its reference block.
+
+use core::ops::{BitXorAssign, Index, IndexMut};
+
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+mod avx2;
+#[cfg(all(feature = "std", target_arch = "aarch64"))]
+mod neon;
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+mod sse2;
+
+/// Size of a block in bytes
+pub(crate) const BLOCK_SIZE: usize = 1024;
+
+/// Number of 64-bit words in a block
+const WORDS_IN_BLOCK: usize = BLOCK_SIZE / 8;
+
+/// A single (1 KiB) memory block, viewed as 128 64-bit words arranged as an
+/// 8x8 grid of 16-byte cells for the purposes of [`Block::fill_block`].
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "zeroize", derive(Zeroize))]
+pub(crate) struct Block([u64; WORDS_IN_BLOCK]);
+
+impl Default for Block {
+    fn default() -> Self {
+        Block([0u64; WORDS_IN_BLOCK])
+    }
+}
+
+impl Block {
+    /// Load a block from a little-endian byte buffer.
+    pub(crate) fn load(&mut self, input: &[u8; BLOCK_SIZE]) {
+        for (word, chunk) in self.0.iter_mut().zip(input.chunks_exact(8)) {
+            *word = u64::from_le_bytes(chunk.try_into().expect("8-byte chunk"));
+        }
+    }
+
+    /// Compress `prev_block` and `ref_block` into `self`: Argon2's `G`
+    /// function.
+    ///
+    /// `prev_block` and `ref_block` are XORed together into a temporary `R`,
+    /// the BlaMka permutation is applied to each of `R`'s 8 rows and then to
+    /// each of its 8 columns, and the result is XORed back over the
+    /// pre-permutation `R` to produce the output. When `with_xor` is set
+    /// (every pass after the first), the existing contents of `self` are
+    /// folded into the output too, per the Argon2 spec.
+    ///
+    /// Dispatches to a SIMD implementation where one is available for the
+    /// target and detected at runtime, falling back to the portable scalar
+    /// implementation from the reference implementation (`ref.c`)
+    /// otherwise.
+    pub(crate) fn fill_block(&mut self, prev_block: Block, ref_block: Block, with_xor: bool) {
+        #[cfg(all(feature = "std", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                // Safety: the feature was just detected at runtime.
+                #[allow(unsafe_code)]
+                return unsafe { avx2::fill_block(self, prev_block, ref_block, with_xor) };
+            }
+
+            if is_x86_feature_detected!("sse2") {
+                // Safety: the feature was just detected at runtime.
+                #[allow(unsafe_code)]
+                return unsafe { sse2::fill_block(self, prev_block, ref_block, with_xor) };
+            }
+        }
+
+        #[cfg(all(feature = "std", target_arch = "aarch64"))]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                // Safety: the feature was just detected at runtime.
+                #[allow(unsafe_code)]
+                return unsafe { neon::fill_block(self, prev_block, ref_block, with_xor) };
+            }
+        }
+
+        fill_block_scalar(self, prev_block, ref_block, with_xor);
+    }
+}
+
+/// Portable scalar fallback for [`Block::fill_block`], ported from the
+/// Argon2 reference implementation's `ref.c`.
+pub(crate) fn fill_block_scalar(dst: &mut Block, prev_block: Block, ref_block: Block, with_xor: bool) {
+    let mut block_r = ref_block;
+    block_r ^= prev_block;
+
+    let mut block_tmp = block_r;
+    if with_xor {
+        block_tmp ^= *dst;
+    }
+
+    for i in 0..8 {
+        blake2_round_on_row(&mut block_r, i);
+    }
+
+    for i in 0..8 {
+        blake2_round_on_column(&mut block_r, i);
+    }
+
+    block_tmp ^= block_r;
+    *dst = block_tmp;
+}
+
+/// Apply one BLAKE2 round to row `row` (cells `[row*8 .. row*8 + 8)`, i.e.
+/// words `[row*16 .. row*16 + 16)`).
+fn blake2_round_on_row(block: &mut Block, row: usize) {
+    let base = row * 16;
+    let mut v = [0u64; 16];
+    v.copy_from_slice(&block.0[base..base + 16]);
+    blake2_round_nomsg(&mut v);
+    block.0[base..base + 16].copy_from_slice(&v);
+}
+
+/// Apply one BLAKE2 round to column `col` (the cells at `[col, col + 8, col
+/// + 16, ..]`, i.e. words `[2*col, 2*col + 1, 2*col + 16, 2*col + 17, ..]`).
+fn blake2_round_on_column(block: &mut Block, col: usize) {
+    let base = col * 2;
+    let mut v = [0u64; 16];
+    for i in 0..8 {
+        v[2 * i] = block.0[base + i * 16];
+        v[2 * i + 1] = block.0[base + i * 16 + 1];
+    }
+    blake2_round_nomsg(&mut v);
+    for i in 0..8 {
+        block.0[base + i * 16] = v[2 * i];
+        block.0[base + i * 16 + 1] = v[2 * i + 1];
+    }
+}
+
+/// The unkeyed BLAKE2b round function (`BLAKE2_ROUND_NOMSG` in the
+/// reference implementation), viewing `v` as a 4x4 matrix in column-major
+/// order and mixing first its columns, then its diagonals.
+fn blake2_round_nomsg(v: &mut [u64; 16]) {
+    g(v, 0, 4, 8, 12);
+    g(v, 1, 5, 9, 13);
+    g(v, 2, 6, 10, 14);
+    g(v, 3, 7, 11, 15);
+
+    g(v, 0, 5, 10, 15);
+    g(v, 1, 6, 11, 12);
+    g(v, 2, 7, 8, 13);
+    g(v, 3, 4, 9, 14);
+}
+
+/// The BlaMka quarter-round: BLAKE2b's `G` with the `a += b` steps replaced
+/// by `fBlaMka(a, b) = a + b + 2 * lo32(a) * lo32(b)`.
+fn g(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize) {
+    v[a] = fblamka(v[a], v[b]);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+
+    v[c] = fblamka(v[c], v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+
+    v[a] = fblamka(v[a], v[b]);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+
+    v[c] = fblamka(v[c], v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+/// `fBlaMka(x, y) = x + y + 2 * lo32(x) * lo32(y)`
+#[inline(always)]
+fn fblamka(x: u64, y: u64) -> u64 {
+    let xy = (x as u32 as u64) * (y as u32 as u64);
+    x.wrapping_add(y).wrapping_add(xy.wrapping_mul(2))
+}
+
+impl BitXorAssign for Block {
+    fn bitxor_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a ^= b;
+        }
+    }
+}
+
+impl Index<usize> for Block {
+    type Output = u64;
+
+    fn index(&self, idx: usize) -> &u64 {
+        &self.0[idx]
+    }
+}
+
+impl IndexMut<usize> for Block {
+    fn index_mut(&mut self, idx: usize) -> &mut u64 {
+        &mut self.0[idx]
+    }
+}
+
+impl Block {
+    /// Iterate over the block's 64-bit words.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &u64> {
+        self.0.iter()
+    }
+
+    /// The block's little-endian byte representation, the inverse of
+    /// [`Block::load`].
+    ///
+    /// Only used by [`crate::MemoryView::block_bytes`], which is `kat`-only.
+    #[cfg(feature = "kat")]
+    pub(crate) fn to_le_bytes(self) -> [u8; BLOCK_SIZE] {
+        let mut out = [0u8; BLOCK_SIZE];
+        for (chunk, word) in out.chunks_exact_mut(8).zip(self.0.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        out
+    }
+}
@@ -0,0 +1,125 @@
+//! AVX2 fast path for [`Block::fill_block`].
+//!
+//! Each row (or column) pass of Argon2's `G` function is 4 independent
+//! BlaMka quarter-rounds. AVX2's 4 64-bit lanes let all 4 run in a single
+//! `__m256i` per operand: the column pass loads `v[0..4]`, `v[4..8]`,
+//! `v[8..12]`, `v[12..16]` directly, and the diagonal pass rotates each of
+//! those loads into place with `_mm256_permute4x64_epi64` instead of the
+//! scalar shuffling the reference implementation does.
+
+use core::arch::x86_64::*;
+
+use super::Block;
+
+#[target_feature(enable = "avx2")]
+pub(super) unsafe fn fill_block(dst: &mut Block, prev_block: Block, ref_block: Block, with_xor: bool) {
+    let mut block_r = ref_block;
+    block_r ^= prev_block;
+
+    let mut block_tmp = block_r;
+    if with_xor {
+        block_tmp ^= *dst;
+    }
+
+    for row in 0..8 {
+        round16(&mut block_r.0[row * 16..row * 16 + 16]);
+    }
+
+    for col in 0..8 {
+        let mut v = [0u64; 16];
+        for row in 0..8 {
+            v[row * 2] = block_r.0[row * 16 + col * 2];
+            v[row * 2 + 1] = block_r.0[row * 16 + col * 2 + 1];
+        }
+        round16(&mut v);
+        for row in 0..8 {
+            block_r.0[row * 16 + col * 2] = v[row * 2];
+            block_r.0[row * 16 + col * 2 + 1] = v[row * 2 + 1];
+        }
+    }
+
+    block_tmp ^= block_r;
+    *dst = block_tmp;
+}
+
+/// Diagonalize rotation amounts, as `_mm256_permute4x64_epi64` immediates:
+/// lane `i` of the result takes lane `(i + k) % 4` of the input.
+const ROTATE_1: i32 = 0b00_11_10_01; // 0x39
+const ROTATE_2: i32 = 0b01_00_11_10; // 0x4e
+const ROTATE_3: i32 = 0b10_01_00_11; // 0x93
+
+/// The unkeyed BLAKE2b round function over 16 words, viewed as a 4x4
+/// column-major matrix: mix the 4 columns, then the 4 diagonals, 4-way
+/// vectorized.
+#[target_feature(enable = "avx2")]
+unsafe fn round16(v: &mut [u64]) {
+    let mut va = load4(v, 0);
+    let mut vb = load4(v, 4);
+    let mut vc = load4(v, 8);
+    let mut vd = load4(v, 12);
+
+    // Columns: G(v0,v4,v8,v12), G(v1,v5,v9,v13), G(v2,v6,v10,v14), G(v3,v7,v11,v15)
+    g(&mut va, &mut vb, &mut vc, &mut vd);
+
+    // Diagonalize: rotate b, c, d so lane i holds the i-th diagonal's operand.
+    vb = _mm256_permute4x64_epi64(vb, ROTATE_1);
+    vc = _mm256_permute4x64_epi64(vc, ROTATE_2);
+    vd = _mm256_permute4x64_epi64(vd, ROTATE_3);
+
+    // Diagonals: G(v0,v5,v10,v15), G(v1,v6,v11,v12), G(v2,v7,v8,v13), G(v3,v4,v9,v14)
+    g(&mut va, &mut vb, &mut vc, &mut vd);
+
+    // Undiagonalize back to column order before storing.
+    vb = _mm256_permute4x64_epi64(vb, ROTATE_3);
+    vc = _mm256_permute4x64_epi64(vc, ROTATE_2);
+    vd = _mm256_permute4x64_epi64(vd, ROTATE_1);
+
+    store4(v, 0, va);
+    store4(v, 4, vb);
+    store4(v, 8, vc);
+    store4(v, 12, vd);
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn load4(v: &[u64], base: usize) -> __m256i {
+    _mm256_loadu_si256(v[base..base + 4].as_ptr() as *const __m256i)
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn store4(v: &mut [u64], base: usize, vec: __m256i) {
+    _mm256_storeu_si256(v[base..base + 4].as_mut_ptr() as *mut __m256i, vec)
+}
+
+/// BlaMka quarter-round, packed four-wide.
+#[target_feature(enable = "avx2")]
+unsafe fn g(a: &mut __m256i, b: &mut __m256i, c: &mut __m256i, d: &mut __m256i) {
+    *a = fblamka(*a, *b);
+    *d = rotr(_mm256_xor_si256(*d, *a), 32);
+
+    *c = fblamka(*c, *d);
+    *b = rotr(_mm256_xor_si256(*b, *c), 24);
+
+    *a = fblamka(*a, *b);
+    *d = rotr(_mm256_xor_si256(*d, *a), 16);
+
+    *c = fblamka(*c, *d);
+    *b = rotr(_mm256_xor_si256(*b, *c), 63);
+}
+
+/// `fBlaMka(x, y) = x + y + 2 * lo32(x) * lo32(y)`, computed per-lane.
+///
+/// `_mm256_mul_epu32` multiplies the low 32 bits of each 64-bit lane, which
+/// is exactly `lo32(x) * lo32(y)` here.
+#[target_feature(enable = "avx2")]
+unsafe fn fblamka(x: __m256i, y: __m256i) -> __m256i {
+    let xy = _mm256_slli_epi64(_mm256_mul_epu32(x, y), 1);
+    _mm256_add_epi64(_mm256_add_epi64(x, y), xy)
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn rotr(x: __m256i, n: u32) -> __m256i {
+    _mm256_or_si256(
+        _mm256_srli_epi64(x, n as i32),
+        _mm256_slli_epi64(x, 64 - n as i32),
+    )
+}
@@ -0,0 +1,136 @@
+//! NEON fast path for [`Block::fill_block`].
+//!
+//! Mirrors the SSE2 implementation: NEON's `uint64x2_t` gives two 64-bit
+//! lanes, so each vector call here packs a pair of Argon2's 4 independent
+//! per-row (or per-column) BlaMka quarter-rounds into one operand.
+
+use core::arch::aarch64::*;
+
+use super::Block;
+
+#[target_feature(enable = "neon")]
+pub(super) unsafe fn fill_block(dst: &mut Block, prev_block: Block, ref_block: Block, with_xor: bool) {
+    let mut block_r = ref_block;
+    block_r ^= prev_block;
+
+    let mut block_tmp = block_r;
+    if with_xor {
+        block_tmp ^= *dst;
+    }
+
+    for row in 0..8 {
+        round16(&mut block_r.0[row * 16..row * 16 + 16]);
+    }
+
+    for col in 0..8 {
+        let mut v = [0u64; 16];
+        for row in 0..8 {
+            v[row * 2] = block_r.0[row * 16 + col * 2];
+            v[row * 2 + 1] = block_r.0[row * 16 + col * 2 + 1];
+        }
+        round16(&mut v);
+        for row in 0..8 {
+            block_r.0[row * 16 + col * 2] = v[row * 2];
+            block_r.0[row * 16 + col * 2 + 1] = v[row * 2 + 1];
+        }
+    }
+
+    block_tmp ^= block_r;
+    *dst = block_tmp;
+}
+
+/// The unkeyed BLAKE2b round function over 16 words, viewed as a 4x4
+/// column-major matrix: mix the 4 columns, then the 4 diagonals. Each of
+/// the two passes mixes two independent quarter-rounds per vector call.
+#[target_feature(enable = "neon")]
+unsafe fn round16(v: &mut [u64]) {
+    quarter_round_pair(v, [0, 1], [4, 5], [8, 9], [12, 13]);
+    quarter_round_pair(v, [2, 3], [6, 7], [10, 11], [14, 15]);
+
+    quarter_round_pair(v, [0, 1], [5, 6], [10, 11], [15, 12]);
+    quarter_round_pair(v, [2, 3], [7, 4], [8, 9], [13, 14]);
+}
+
+#[target_feature(enable = "neon")]
+unsafe fn quarter_round_pair(v: &mut [u64], a: [usize; 2], b: [usize; 2], c: [usize; 2], d: [usize; 2]) {
+    let mut va = load2(v, a);
+    let mut vb = load2(v, b);
+    let mut vc = load2(v, c);
+    let mut vd = load2(v, d);
+
+    g(&mut va, &mut vb, &mut vc, &mut vd);
+
+    store2(v, a, va);
+    store2(v, b, vb);
+    store2(v, c, vc);
+    store2(v, d, vd);
+}
+
+#[target_feature(enable = "neon")]
+unsafe fn load2(v: &[u64], idx: [usize; 2]) -> uint64x2_t {
+    let pair = [v[idx[0]], v[idx[1]]];
+    vld1q_u64(pair.as_ptr())
+}
+
+#[target_feature(enable = "neon")]
+unsafe fn store2(v: &mut [u64], idx: [usize; 2], vec: uint64x2_t) {
+    let mut pair = [0u64; 2];
+    vst1q_u64(pair.as_mut_ptr(), vec);
+    v[idx[0]] = pair[0];
+    v[idx[1]] = pair[1];
+}
+
+/// BlaMka quarter-round, packed two-wide.
+#[target_feature(enable = "neon")]
+unsafe fn g(a: &mut uint64x2_t, b: &mut uint64x2_t, c: &mut uint64x2_t, d: &mut uint64x2_t) {
+    *a = fblamka(*a, *b);
+    *d = rotr(veorq_u64(*d, *a), 32);
+
+    *c = fblamka(*c, *d);
+    *b = rotr(veorq_u64(*b, *c), 24);
+
+    *a = fblamka(*a, *b);
+    *d = rotr(veorq_u64(*d, *a), 16);
+
+    *c = fblamka(*c, *d);
+    *b = rotr(veorq_u64(*b, *c), 63);
+}
+
+/// `fBlaMka(x, y) = x + y + 2 * lo32(x) * lo32(y)`, computed per-lane.
+///
+/// `vmull_u32` widening-multiplies the low 32 bits of each lane directly
+/// into a 64-bit result, which is exactly `lo32(x) * lo32(y)` here.
+#[target_feature(enable = "neon")]
+unsafe fn fblamka(x: uint64x2_t, y: uint64x2_t) -> uint64x2_t {
+    let x_lo = vmovn_u64(x);
+    let y_lo = vmovn_u64(y);
+    let xy = vshlq_n_u64(vmull_u32(x_lo, y_lo), 1);
+    vaddq_u64(vaddq_u64(x, y), xy)
+}
+
+#[target_feature(enable = "neon")]
+unsafe fn rotr(x: uint64x2_t, n: i32) -> uint64x2_t {
+    vorrq_u64(vshrq_n_u64_dyn(x, n), vshlq_n_u64_dyn(x, 64 - n))
+}
+
+// NEON's `vshrq_n_u64`/`vshlq_n_u64` require a compile-time shift amount;
+// our rotation amounts (32, 24, 16, 63) are all call-site constants, but
+// routed through a plain `u64` shift here keeps `rotr` a single generic
+// helper rather than four near-duplicate ones.
+#[target_feature(enable = "neon")]
+unsafe fn vshrq_n_u64_dyn(x: uint64x2_t, n: i32) -> uint64x2_t {
+    let mut buf = [0u64; 2];
+    vst1q_u64(buf.as_mut_ptr(), x);
+    buf[0] >>= n;
+    buf[1] >>= n;
+    vld1q_u64(buf.as_ptr())
+}
+
+#[target_feature(enable = "neon")]
+unsafe fn vshlq_n_u64_dyn(x: uint64x2_t, n: i32) -> uint64x2_t {
+    let mut buf = [0u64; 2];
+    vst1q_u64(buf.as_mut_ptr(), x);
+    buf[0] <<= n;
+    buf[1] <<= n;
+    vld1q_u64(buf.as_ptr())
+}
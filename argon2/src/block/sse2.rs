@@ -0,0 +1,110 @@
+//! SSE2 fast path for [`Block::fill_block`].
+//!
+//! Argon2's `G` function runs 4 independent BlaMka quarter-rounds per row
+//! (or column) pass. SSE2 only gives us two 64-bit lanes, so each vector
+//! call here carries a *pair* of those independent quarter-rounds packed
+//! into one `__m128i` per operand, halving the scalar instruction count.
+
+use core::arch::x86_64::*;
+
+use super::Block;
+
+#[target_feature(enable = "sse2")]
+pub(super) unsafe fn fill_block(dst: &mut Block, prev_block: Block, ref_block: Block, with_xor: bool) {
+    let mut block_r = ref_block;
+    block_r ^= prev_block;
+
+    let mut block_tmp = block_r;
+    if with_xor {
+        block_tmp ^= *dst;
+    }
+
+    for row in 0..8 {
+        round16(&mut block_r.0[row * 16..row * 16 + 16]);
+    }
+
+    for col in 0..8 {
+        let mut v = [0u64; 16];
+        for row in 0..8 {
+            v[row * 2] = block_r.0[row * 16 + col * 2];
+            v[row * 2 + 1] = block_r.0[row * 16 + col * 2 + 1];
+        }
+        round16(&mut v);
+        for row in 0..8 {
+            block_r.0[row * 16 + col * 2] = v[row * 2];
+            block_r.0[row * 16 + col * 2 + 1] = v[row * 2 + 1];
+        }
+    }
+
+    block_tmp ^= block_r;
+    *dst = block_tmp;
+}
+
+/// The unkeyed BLAKE2b round function over 16 words, viewed as a 4x4
+/// column-major matrix: mix the 4 columns, then the 4 diagonals. Each of
+/// the two passes mixes two independent quarter-rounds per vector call.
+#[target_feature(enable = "sse2")]
+unsafe fn round16(v: &mut [u64]) {
+    quarter_round_pair(v, [0, 1], [4, 5], [8, 9], [12, 13]);
+    quarter_round_pair(v, [2, 3], [6, 7], [10, 11], [14, 15]);
+
+    quarter_round_pair(v, [0, 1], [5, 6], [10, 11], [15, 12]);
+    quarter_round_pair(v, [2, 3], [7, 4], [8, 9], [13, 14]);
+}
+
+#[target_feature(enable = "sse2")]
+unsafe fn quarter_round_pair(v: &mut [u64], a: [usize; 2], b: [usize; 2], c: [usize; 2], d: [usize; 2]) {
+    let mut va = load2(v, a);
+    let mut vb = load2(v, b);
+    let mut vc = load2(v, c);
+    let mut vd = load2(v, d);
+
+    g(&mut va, &mut vb, &mut vc, &mut vd);
+
+    store2(v, a, va);
+    store2(v, b, vb);
+    store2(v, c, vc);
+    store2(v, d, vd);
+}
+
+#[target_feature(enable = "sse2")]
+unsafe fn load2(v: &[u64], idx: [usize; 2]) -> __m128i {
+    _mm_set_epi64x(v[idx[1]] as i64, v[idx[0]] as i64)
+}
+
+#[target_feature(enable = "sse2")]
+unsafe fn store2(v: &mut [u64], idx: [usize; 2], vec: __m128i) {
+    v[idx[0]] = _mm_cvtsi128_si64(vec) as u64;
+    v[idx[1]] = _mm_cvtsi128_si64(_mm_unpackhi_epi64(vec, vec)) as u64;
+}
+
+/// BlaMka quarter-round, packed two-wide.
+#[target_feature(enable = "sse2")]
+unsafe fn g(a: &mut __m128i, b: &mut __m128i, c: &mut __m128i, d: &mut __m128i) {
+    *a = fblamka(*a, *b);
+    *d = rotr(_mm_xor_si128(*d, *a), 32);
+
+    *c = fblamka(*c, *d);
+    *b = rotr(_mm_xor_si128(*b, *c), 24);
+
+    *a = fblamka(*a, *b);
+    *d = rotr(_mm_xor_si128(*d, *a), 16);
+
+    *c = fblamka(*c, *d);
+    *b = rotr(_mm_xor_si128(*b, *c), 63);
+}
+
+/// `fBlaMka(x, y) = x + y + 2 * lo32(x) * lo32(y)`, computed per-lane.
+///
+/// `_mm_mul_epu32` multiplies the low 32 bits of each 64-bit lane, which is
+/// exactly `lo32(x) * lo32(y)` here.
+#[target_feature(enable = "sse2")]
+unsafe fn fblamka(x: __m128i, y: __m128i) -> __m128i {
+    let xy = _mm_slli_epi64(_mm_mul_epu32(x, y), 1);
+    _mm_add_epi64(_mm_add_epi64(x, y), xy)
+}
+
+#[target_feature(enable = "sse2")]
+unsafe fn rotr(x: __m128i, n: u32) -> __m128i {
+    _mm_or_si128(_mm_srli_epi64(x, n as i32), _mm_slli_epi64(x, 64 - n as i32))
+}
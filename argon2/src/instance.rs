@@ -1,20 +1,14 @@
 //! Argon2 instance (i.e. state)
 
 use crate::{
-    Algorithm, Argon2, Block, Error, Memory, Version, BLOCK_SIZE, MAX_OUTLEN, MIN_OUTLEN,
+    Algorithm, Argon2, Block, Error, MemoryBlocks, Version, BLOCK_SIZE, MAX_OUTLEN, MIN_OUTLEN,
     SYNC_POINTS,
 };
 use blake2::{
     digest::{self, VariableOutput},
     Blake2b, Digest, VarBlake2b,
 };
-
-#[cfg(feature = "parallel")]
-use {
-    alloc::vec::Vec,
-    core::mem,
-    rayon::iter::{ParallelBridge, ParallelIterator},
-};
+use core::marker::PhantomData;
 
 #[cfg(feature = "zeroize")]
 use zeroize::Zeroize;
@@ -37,14 +31,96 @@ struct Position {
     index: u32,
 }
 
+/// Hook for observing intermediate Argon2 state, gated behind the `kat`
+/// feature.
+///
+/// Implement this to capture `H0` and the per-pass memory state at the same
+/// points the reference implementation's `GENKAT note` markers dump them,
+/// so it can be diffed against the official Argon2 known-answer-test (KAT)
+/// vectors, e.g. to debug a parameter or endianness regression.
+#[cfg(feature = "kat")]
+pub trait Argon2Trace {
+    /// Called once, right after `H0` has been computed.
+    fn initial(&mut self, h0: &[u8]);
+
+    /// Called after every full pass over memory.
+    fn pass(&mut self, pass: u32, memory: MemoryView<'_>);
+}
+
+/// Read-only, byte-oriented view of the working memory handed to
+/// [`Argon2Trace::pass`].
+///
+/// Wraps the crate-internal [`MemoryBlocks`] trait so KAT implementors
+/// outside this crate get a way to read out block contents to diff
+/// against the Argon2 reference vectors, without needing to name (or have
+/// visibility into) `MemoryBlocks` itself.
+#[cfg(feature = "kat")]
+pub struct MemoryView<'a>(&'a dyn MemoryBlocks);
+
+#[cfg(feature = "kat")]
+impl MemoryView<'_> {
+    /// Number of blocks in memory.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if there are no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The little-endian bytes of the block at `idx`, matching the layout
+    /// the Argon2 KAT vectors dump.
+    pub fn block_bytes(&self, idx: usize) -> [u8; BLOCK_SIZE] {
+        self.0.get_block(idx).to_le_bytes()
+    }
+}
+
+/// A borrowed [`Argon2Trace`] hook, or a zero-cost no-op when the `kat`
+/// feature is disabled.
+#[cfg(feature = "kat")]
+pub(crate) type Trace<'a> = Option<&'a mut dyn Argon2Trace>;
+
+/// A borrowed [`Argon2Trace`] hook, or a zero-cost no-op when the `kat`
+/// feature is disabled.
+#[cfg(not(feature = "kat"))]
+pub(crate) type Trace<'a> = ();
+
+#[cfg(feature = "kat")]
+fn emit_initial(trace: &mut Trace<'_>, h0: &[u8]) {
+    if let Some(t) = trace {
+        t.initial(h0);
+    }
+}
+
+#[cfg(not(feature = "kat"))]
+fn emit_initial(_trace: &mut Trace<'_>, _h0: &[u8]) {}
+
+#[cfg(feature = "kat")]
+fn emit_pass(trace: &mut Trace<'_>, pass: u32, memory: &dyn MemoryBlocks) {
+    if let Some(t) = trace {
+        t.pass(pass, MemoryView(memory));
+    }
+}
+
+#[cfg(not(feature = "kat"))]
+fn emit_pass(_trace: &mut Trace<'_>, _pass: u32, _memory: &dyn MemoryBlocks) {}
+
 /// Argon2 instance: memory pointer, number of passes, amount of memory, type,
 /// and derived values.
 ///
 /// Used to evaluate the number and location of blocks to construct in each
 /// thread.
-pub(crate) struct Instance<'a> {
+///
+/// Generic over the working-memory backing `M`: the default slice-backed
+/// `Memory`, or any other [`MemoryBlocks`] implementation a caller plugs
+/// in (e.g. a `memmap2`-backed region for large `m_cost`, or a pooled
+/// arena reused across hashes). Parallel filling needs `M` to also support
+/// [`MemoryBlocks::split_lanes_mut`]; backings that don't implement it
+/// fall back to filling on a single thread.
+pub(crate) struct Instance<'a, M: MemoryBlocks> {
     /// Memory blocks
-    memory: Memory<'a>,
+    memory: M,
 
     /// Version
     version: Version,
@@ -63,19 +139,28 @@ pub(crate) struct Instance<'a> {
 
     /// Argon2 type
     alg: Algorithm,
+
+    /// KAT/conformance trace hook (see [`Argon2Trace`]); a no-op unit value
+    /// unless the `kat` feature is enabled.
+    trace: Trace<'a>,
+
+    /// Ties `Instance` to `'a` even when `kat` is disabled and `Trace<'a>`
+    /// is the no-op `()`, which otherwise leaves `'a` unused by any field.
+    _trace_lifetime: PhantomData<&'a ()>,
 }
 
-impl<'a> Instance<'a> {
+impl<'a, M: MemoryBlocks> Instance<'a, M> {
     /// Hash the given inputs with Argon2, writing the output into the
     /// provided buffer.
     pub fn hash(
         context: &Argon2<'_>,
         alg: Algorithm,
         initial_hash: digest::Output<Blake2b>,
-        memory: Memory<'a>,
+        memory: M,
         out: &mut [u8],
+        trace: Trace<'a>,
     ) -> Result<(), Error> {
-        let mut instance = Self::new(context, alg, initial_hash, memory)?;
+        let mut instance = Self::new(context, alg, initial_hash, memory, trace)?;
 
         // Filling memory
         instance.fill_memory_blocks();
@@ -86,13 +171,18 @@ impl<'a> Instance<'a> {
 
     /// Hashes the inputs with BLAKE2b and creates first two blocks.
     ///
+    /// `initial_hash` is `H0` as computed by [`initial_hash`] (which already
+    /// folds in the `secret`/`ad` Argon2 parameters, if any); this is where
+    /// it's consumed and zeroized.
+    ///
     /// Returns struct containing main memory with 2 blocks per lane initialized.
     #[allow(unused_mut)]
     fn new(
         context: &Argon2<'_>,
         alg: Algorithm,
         mut initial_hash: digest::Output<Blake2b>,
-        memory: Memory<'a>,
+        memory: M,
+        trace: Trace<'a>,
     ) -> Result<Self, Error> {
         let lane_length = memory.segment_length() * SYNC_POINTS;
 
@@ -104,6 +194,8 @@ impl<'a> Instance<'a> {
             lanes: context.lanes,
             threads: context.threads,
             alg,
+            trace,
+            _trace_lifetime: PhantomData,
         };
 
         if instance.threads > instance.lanes {
@@ -111,6 +203,7 @@ impl<'a> Instance<'a> {
         }
 
         // GENKAT note: this is where `initial_kat` would be called
+        emit_initial(&mut instance.trace, &initial_hash);
 
         // Creating first blocks, we always have at least two blocks in a slice
         instance.fill_first_blocks(&initial_hash)?;
@@ -121,59 +214,90 @@ impl<'a> Instance<'a> {
         Ok(instance)
     }
 
-    /// Create multiple mutable references for the current instance, one for every lane
+    /// Tries to fill memory across lanes in parallel using `rayon::scope`,
+    /// one task per lane per sync point, each given a genuinely
+    /// non-aliasing `&mut [Block]` via [`MemoryBlocks::split_lanes_mut`].
+    ///
+    /// Returns `false` without having filled anything if `self.memory`
+    /// can't be split this way (in which case the caller falls back to
+    /// filling serially) or if there's only one thread to use anyway.
     #[cfg(feature = "parallel")]
-    #[allow(unsafe_code)]
-    unsafe fn mut_self_refs(&mut self) -> Vec<usize> {
-        let lanes = self.lanes;
-        // This transmute can be skipped when a scoped threadpool is used (or when `spawn_unchecked()` gets stabilised)
-        let this = mem::transmute::<_, &mut Instance<'static>>(self);
-        let this: *mut Instance<'static> = this;
-        let this = this as usize;
+    fn fill_memory_blocks_par(&mut self) -> bool {
+        if self.threads <= 1 {
+            return false;
+        }
 
-        // Dereference the raw pointer multiple times to create multiple mutable references
-        core::iter::repeat(this).take(lanes as usize).collect()
-    }
+        let lanes = self.lanes;
+        let lane_length = self.lane_length;
+        let version = self.version;
+        let alg = self.alg;
+        let passes = self.passes;
 
-    #[cfg(feature = "parallel")]
-    fn fill_memory_blocks_par(&mut self) {
-        for r in 0..self.passes {
+        for r in 0..passes {
             for s in 0..SYNC_POINTS {
-                // Safety: - All threads that receive a references will be joined before the item gets dropped
-                //         - All the read and write operations *shouldn't* overlap
+                // Safety: see the invariant documented on `LaneMemory`. A
+                // lane only ever reads blocks belonging to other lanes'
+                // already-completed segments, never one another lane is
+                // concurrently writing this sync point, so sharing this
+                // read-only pointer across the scope below never races
+                // with the writes each lane does through its own disjoint
+                // `own` slice.
                 #[allow(unsafe_code)]
-                let self_refs = unsafe { self.mut_self_refs() };
-
-                (0..self.lanes)
-                    .zip(self_refs)
-                    .par_bridge()
-                    .for_each(|(l, self_ref)| {
-                        #[allow(unsafe_code)]
-                        let self_ref = unsafe { &mut *(self_ref as *mut Instance<'static>) };
-
-                        self_ref.fill_segment(Position {
-                            pass: r,
-                            lane: l,
-                            slice: s,
-                            index: 0,
+                let (all_blocks, own_slices) = match self.memory.split_lanes_mut(lane_length) {
+                    Some(split) => split,
+                    // Backings either always or never support splitting,
+                    // so this only ever triggers at (r, s) == (0, 0),
+                    // before any work has been done.
+                    None => return false,
+                };
+
+                rayon::scope(|scope| {
+                    for (l, own) in own_slices.into_iter().enumerate() {
+                        let mut lane_memory = LaneMemory {
+                            own,
+                            all_blocks,
+                            lane: l as u32,
+                            lanes,
+                            lane_length,
+                        };
+
+                        scope.spawn(move |_| {
+                            fill_segment(
+                                &mut lane_memory,
+                                version,
+                                alg,
+                                passes,
+                                lanes,
+                                lane_length,
+                                Position {
+                                    pass: r,
+                                    lane: l as u32,
+                                    slice: s,
+                                    index: 0,
+                                },
+                            );
                         });
-                    });
+                    }
+                });
             }
 
             // GENKAT note: this is where `internal_kat` would be called
+            emit_pass(&mut self.trace, r, &self.memory);
         }
+
+        true
     }
 
     /// Function that fills the entire memory t_cost times based on the first two
     /// blocks in each lane
     fn fill_memory_blocks(&mut self) {
         #[cfg(feature = "parallel")]
-        if self.threads > 1 {
-            self.fill_memory_blocks_par();
+        if self.fill_memory_blocks_par() {
             return;
         }
 
-        // Single-threaded version for p=1 case
+        // Single-threaded version, used for p=1 and for any `MemoryBlocks`
+        // backing that can't be split into disjoint per-lane slices
         for r in 0..self.passes {
             for s in 0..SYNC_POINTS {
                 for l in 0..self.lanes {
@@ -187,6 +311,7 @@ impl<'a> Instance<'a> {
             }
 
             // GENKAT note: this is where `internal_kat` would be called
+            emit_pass(&mut self.trace, r, &self.memory);
         }
     }
 
@@ -237,168 +362,327 @@ impl<'a> Instance<'a> {
     }
 
     /// Function that fills the segment using previous segments
-    // TODO(tarcieri): optimized implementation (i.e. from opt.c instead of ref.c)
-    fn fill_segment(&mut self, mut position: Position) {
-        let mut address_block = Block::default();
-        let mut input_block = Block::default();
-        let zero_block = Block::default();
+    fn fill_segment(&mut self, position: Position) {
+        fill_segment(
+            &mut self.memory,
+            self.version,
+            self.alg,
+            self.passes,
+            self.lanes,
+            self.lane_length,
+            position,
+        )
+    }
+}
 
-        let data_independent_addressing = (self.alg == Algorithm::Argon2i)
-            || (self.alg == Algorithm::Argon2id
-                && (position.pass == 0)
-                && (position.slice < SYNC_POINTS / 2));
+/// View of the working memory handed to a single lane's worker while
+/// filling blocks in parallel.
+///
+/// Writes only ever land in `own`, a contiguous sub-slice this lane holds
+/// exclusively (lanes are laid out contiguously in `Memory`, so
+/// `own` and every other lane's sub-slice are disjoint), which is what
+/// lets `fill_memory_blocks_par` hand out genuinely non-aliasing `&mut
+/// [Block]`s instead of forging a `'static` reference to the whole
+/// `Instance`. Reads may still need blocks from other lanes, which go
+/// through `all_blocks`, a read-only pointer to the full memory region.
+///
+/// # Safety
+///
+/// Per the reference schedule computed in `index_alpha`, a lane only ever
+/// reads blocks belonging to *already-completed* segments of other lanes,
+/// never the segment those lanes are concurrently writing this sync
+/// point. Those blocks do lie inside the other worker's `own` slice (which
+/// spans its whole lane), but that worker only writes its current
+/// segment, so no block is read through `all_blocks` while another thread
+/// writes it.
+#[cfg(feature = "parallel")]
+struct LaneMemory<'a> {
+    own: &'a mut [Block],
+    all_blocks: *const Block,
+    lane: u32,
+    lanes: u32,
+    lane_length: u32,
+}
 
-        if data_independent_addressing {
-            input_block[0] = position.pass as u64;
-            input_block[1] = position.lane as u64;
-            input_block[2] = position.slice as u64;
-            input_block[3] = self.memory.len() as u64;
-            input_block[4] = self.passes as u64;
-            input_block[5] = self.alg as u64;
-        }
+// Safety: `all_blocks` is only ever dereferenced (in `get_block`) to read
+// blocks belonging to another lane's already-completed segment — never the
+// segment that lane's worker is writing through its `own` slice — per the
+// invariant documented on `LaneMemory`. So handing a `LaneMemory` to another thread never lets two
+// threads race on the same block, which is what `Send` needs here.
+#[cfg(feature = "parallel")]
+#[allow(unsafe_code)]
+unsafe impl Send for LaneMemory<'_> {}
 
-        let mut starting_index = 0;
+#[cfg(feature = "parallel")]
+impl LaneMemory<'_> {
+    /// Index into `own`, if `idx` falls within this lane's range.
+    fn local_index(&self, idx: usize) -> Option<usize> {
+        idx.checked_sub((self.lane * self.lane_length) as usize)
+            .filter(|&local| local < self.lane_length as usize)
+    }
+}
 
-        if position.pass == 0 && position.slice == 0 {
-            starting_index = 2; // we have already generated the first two blocks
+#[cfg(feature = "parallel")]
+#[allow(unsafe_code)]
+impl MemoryBlocks for LaneMemory<'_> {
+    fn len(&self) -> usize {
+        self.lanes as usize * self.lane_length as usize
+    }
 
-            // Don't forget to generate the first block of addresses
-            if data_independent_addressing {
-                next_addresses(&mut address_block, &mut input_block, &zero_block);
-            }
+    fn get_block(&self, idx: usize) -> Block {
+        match self.local_index(idx) {
+            Some(local) => self.own[local],
+            // Safety: see the invariant documented on `LaneMemory`.
+            None => unsafe { *self.all_blocks.add(idx) },
         }
+    }
 
-        // Offset of the current block
-        let mut curr_offset = position.lane * self.lane_length
-            + position.slice * self.memory.segment_length()
-            + starting_index;
-
-        let mut prev_offset = if 0 == curr_offset % self.lane_length {
-            // Last block in this lane
-            curr_offset + self.lane_length - 1
-        } else {
-            // Previous block
-            curr_offset - 1
-        };
+    fn get_block_mut(&mut self, idx: usize) -> &mut Block {
+        let local = self
+            .local_index(idx)
+            .expect("segment filling only ever writes within its own lane");
+        &mut self.own[local]
+    }
 
-        for i in starting_index..self.memory.segment_length() {
-            // 1.1 Rotating prev_offset if needed
-            if curr_offset % self.lane_length == 1 {
-                prev_offset = curr_offset - 1;
-            }
+    fn segment_length(&self) -> u32 {
+        self.lane_length / SYNC_POINTS
+    }
+}
 
-            // 1.2 Computing the index of the reference block
-            // 1.2.1 Taking pseudo-random value from the previous block
-            let pseudo_rand = if data_independent_addressing {
-                if i % ADDRESSES_IN_BLOCK == 0 {
-                    next_addresses(&mut address_block, &mut input_block, &zero_block);
-                }
-                address_block[(i % ADDRESSES_IN_BLOCK) as usize]
-            } else {
-                self.memory.get_block(prev_offset as usize)[0]
-            };
+/// Function that fills the segment using previous segments
+#[allow(clippy::too_many_arguments)]
+fn fill_segment<S: MemoryBlocks>(
+    memory: &mut S,
+    version: Version,
+    alg: Algorithm,
+    passes: u32,
+    lanes: u32,
+    lane_length: u32,
+    mut position: Position,
+) {
+    let segment_length = memory.segment_length();
+    let memory_len = memory.len();
+
+    let mut address_block = Block::default();
+    let mut input_block = Block::default();
+    let zero_block = Block::default();
+
+    let data_independent_addressing = (alg == Algorithm::Argon2i)
+        || (alg == Algorithm::Argon2id
+            && (position.pass == 0)
+            && (position.slice < SYNC_POINTS / 2));
+
+    if data_independent_addressing {
+        input_block[0] = position.pass as u64;
+        input_block[1] = position.lane as u64;
+        input_block[2] = position.slice as u64;
+        input_block[3] = memory_len as u64;
+        input_block[4] = passes as u64;
+        input_block[5] = alg as u64;
+    }
 
-            // 1.2.2 Computing the lane of the reference block
-            let mut ref_lane = (pseudo_rand >> 32) as u32 % self.lanes;
+    let mut starting_index = 0;
 
-            if position.pass == 0 && position.slice == 0 {
-                // Can not reference other lanes yet
-                ref_lane = position.lane;
-            }
+    if position.pass == 0 && position.slice == 0 {
+        starting_index = 2; // we have already generated the first two blocks
 
-            // 1.2.3 Computing the number of possible reference block within the lane.
-            position.index = i;
-
-            let ref_index = self.index_alpha(
-                position,
-                (pseudo_rand & 0xFFFFFFFF) as u32,
-                ref_lane == position.lane,
-            );
-
-            // 2 Creating a new block
-            let ref_block = self
-                .memory
-                .get_block((self.lane_length * ref_lane + ref_index) as usize);
-            let prev_block = self.memory.get_block(prev_offset as usize);
-
-            // version 1.2.1 and earlier: overwrite, not XOR
-            let without_xor = self.version == Version::V0x10 || position.pass == 0;
-            self.memory.get_block_mut(curr_offset as usize).fill_block(
-                prev_block,
-                ref_block,
-                !without_xor,
-            );
-
-            curr_offset += 1;
-            prev_offset += 1;
+        // Don't forget to generate the first block of addresses
+        if data_independent_addressing {
+            next_addresses(&mut address_block, &mut input_block, &zero_block);
         }
     }
 
-    /// Computes absolute position of reference block in the lane following a skewed
-    /// distribution and using a pseudo-random value as input.
-    ///
-    /// # Params
-    /// - `position`: Pointer to the current position
-    /// - `pseudo_rand`: 32-bit pseudo-random value used to determine the position
-    /// - `same_lane`: Indicates if the block will be taken from the current lane.
-    ///                If so we can reference the current segment.
-    fn index_alpha(&self, position: Position, pseudo_rand: u32, same_lane: bool) -> u32 {
-        // Pass 0:
-        // - This lane: all already finished segments plus already constructed
-        //   blocks in this segment
-        // - Other lanes: all already finished segments
-        //
-        // Pass 1+:
-        // - This lane: (SYNC_POINTS - 1) last segments plus already constructed
-        //   blocks in this segment
-        // - Other lanes : (SYNC_POINTS - 1) last segments
-        let reference_area_size = if 0 == position.pass {
-            // First pass
-            if position.slice == 0 {
-                // First slice
-                position.index - 1 // all but the previous
-            } else if same_lane {
-                // The same lane => add current segment
-                position.slice * self.memory.segment_length() + position.index - 1
-            } else {
-                position.slice * self.memory.segment_length()
-                    - if position.index == 0 { 1 } else { 0 }
+    // Offset of the current block
+    let mut curr_offset =
+        position.lane * lane_length + position.slice * segment_length + starting_index;
+
+    let mut prev_offset = if 0 == curr_offset % lane_length {
+        // Last block in this lane
+        curr_offset + lane_length - 1
+    } else {
+        // Previous block
+        curr_offset - 1
+    };
+
+    for i in starting_index..segment_length {
+        // 1.1 Rotating prev_offset if needed
+        if curr_offset % lane_length == 1 {
+            prev_offset = curr_offset - 1;
+        }
+
+        // 1.2 Computing the index of the reference block
+        // 1.2.1 Taking pseudo-random value from the previous block
+        let pseudo_rand = if data_independent_addressing {
+            if i % ADDRESSES_IN_BLOCK == 0 {
+                next_addresses(&mut address_block, &mut input_block, &zero_block);
             }
+            address_block[(i % ADDRESSES_IN_BLOCK) as usize]
         } else {
-            // Second pass
-            if same_lane {
-                self.lane_length - self.memory.segment_length() + position.index - 1
-            } else {
-                self.lane_length
-                    - self.memory.segment_length()
-                    - if position.index == 0 { 1 } else { 0 }
-            }
+            memory.get_block(prev_offset as usize)[0]
         };
 
-        // 1.2.4. Mapping pseudo_rand to 0..<reference_area_size-1> and produce
-        // relative position
-        let mut relative_position = pseudo_rand as u64;
-        relative_position = (relative_position * relative_position) >> 32;
-        let relative_position = reference_area_size
-            - 1
-            - (((reference_area_size as u64 * relative_position) >> 32) as u32);
-
-        // 1.2.5 Computing starting position
-        let mut start_position = 0;
-
-        if position.pass != 0 {
-            start_position = if position.slice == SYNC_POINTS - 1 {
-                0
-            } else {
-                (position.slice + 1) * self.memory.segment_length()
-            }
+        // 1.2.2 Computing the lane of the reference block
+        let mut ref_lane = (pseudo_rand >> 32) as u32 % lanes;
+
+        if position.pass == 0 && position.slice == 0 {
+            // Can not reference other lanes yet
+            ref_lane = position.lane;
         }
 
-        // 1.2.6. Computing absolute position
-        (start_position + relative_position as u32) % self.lane_length
+        // 1.2.3 Computing the number of possible reference block within the lane.
+        position.index = i;
+
+        let ref_index = index_alpha(
+            position,
+            (pseudo_rand & 0xFFFFFFFF) as u32,
+            ref_lane == position.lane,
+            lane_length,
+            segment_length,
+        );
+
+        // 2 Creating a new block
+        let ref_block = memory.get_block((lane_length * ref_lane + ref_index) as usize);
+        let prev_block = memory.get_block(prev_offset as usize);
+
+        // version 1.2.1 and earlier: overwrite, not XOR
+        let without_xor = version == Version::V0x10 || position.pass == 0;
+        memory
+            .get_block_mut(curr_offset as usize)
+            .fill_block(prev_block, ref_block, !without_xor);
+
+        curr_offset += 1;
+        prev_offset += 1;
     }
 }
 
+/// Computes absolute position of reference block in the lane following a skewed
+/// distribution and using a pseudo-random value as input.
+///
+/// # Params
+/// - `position`: Pointer to the current position
+/// - `pseudo_rand`: 32-bit pseudo-random value used to determine the position
+/// - `same_lane`: Indicates if the block will be taken from the current lane.
+///                If so we can reference the current segment.
+/// - `lane_length`: Number of blocks in a lane
+/// - `segment_length`: Number of blocks in a segment
+fn index_alpha(
+    position: Position,
+    pseudo_rand: u32,
+    same_lane: bool,
+    lane_length: u32,
+    segment_length: u32,
+) -> u32 {
+    // Pass 0:
+    // - This lane: all already finished segments plus already constructed
+    //   blocks in this segment
+    // - Other lanes: all already finished segments
+    //
+    // Pass 1+:
+    // - This lane: (SYNC_POINTS - 1) last segments plus already constructed
+    //   blocks in this segment
+    // - Other lanes : (SYNC_POINTS - 1) last segments
+    let reference_area_size = if 0 == position.pass {
+        // First pass
+        if position.slice == 0 {
+            // First slice
+            position.index - 1 // all but the previous
+        } else if same_lane {
+            // The same lane => add current segment
+            position.slice * segment_length + position.index - 1
+        } else {
+            position.slice * segment_length - if position.index == 0 { 1 } else { 0 }
+        }
+    } else {
+        // Second pass
+        if same_lane {
+            lane_length - segment_length + position.index - 1
+        } else {
+            lane_length - segment_length - if position.index == 0 { 1 } else { 0 }
+        }
+    };
+
+    // 1.2.4. Mapping pseudo_rand to 0..<reference_area_size-1> and produce
+    // relative position
+    let mut relative_position = pseudo_rand as u64;
+    relative_position = (relative_position * relative_position) >> 32;
+    let relative_position = reference_area_size
+        - 1
+        - (((reference_area_size as u64 * relative_position) >> 32) as u32);
+
+    // 1.2.5 Computing starting position
+    let mut start_position = 0;
+
+    if position.pass != 0 {
+        start_position = if position.slice == SYNC_POINTS - 1 {
+            0
+        } else {
+            (position.slice + 1) * segment_length
+        }
+    }
+
+    // 1.2.6. Computing absolute position
+    (start_position + relative_position as u32) % lane_length
+}
+
+/// Compute the initial (pre-block) hash `H0` fed to [`Instance::new`].
+///
+/// Folds the optional secret key `K` and associated data `X` in alongside
+/// the password and salt, per the Argon2 spec:
+///
+/// ```text
+/// H0 = BLAKE2b(LE32(lanes) || LE32(outlen) || LE32(m_cost) || LE32(t_cost)
+///            || LE32(version) || LE32(alg)
+///            || LE32(pwd_len)    || pwd
+///            || LE32(salt_len)   || salt
+///            || LE32(key_len)    || key
+///            || LE32(ad_len)     || ad)
+/// ```
+///
+/// `secret` (the keyed-hashing / "pepper" input) and `ad` both default to
+/// empty when absent, matching a zero-length `key`/`ad` field. This
+/// function only borrows `secret`; [`Argon2::initial_hash`](crate::Argon2::initial_hash)
+/// is the one that zeroizes the caller's buffer (under the `zeroize`
+/// feature) once it's been folded in here. The resulting `H0` is itself
+/// zeroized by [`Instance::new`] once it's been consumed.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn initial_hash(
+    lanes: u32,
+    out_len: u32,
+    m_cost: u32,
+    t_cost: u32,
+    version: Version,
+    alg: Algorithm,
+    pwd: &[u8],
+    salt: &[u8],
+    secret: Option<&[u8]>,
+    ad: Option<&[u8]>,
+) -> digest::Output<Blake2b> {
+    let mut digest = Blake2b::new();
+
+    digest.update(lanes.to_le_bytes());
+    digest.update(out_len.to_le_bytes());
+    digest.update(m_cost.to_le_bytes());
+    digest.update(t_cost.to_le_bytes());
+    digest.update((version as u32).to_le_bytes());
+    digest.update((alg as u32).to_le_bytes());
+
+    digest.update((pwd.len() as u32).to_le_bytes());
+    digest.update(pwd);
+
+    digest.update((salt.len() as u32).to_le_bytes());
+    digest.update(salt);
+
+    let secret = secret.unwrap_or(&[]);
+    digest.update((secret.len() as u32).to_le_bytes());
+    digest.update(secret);
+
+    let ad = ad.unwrap_or(&[]);
+    digest.update((ad.len() as u32).to_le_bytes());
+    digest.update(ad);
+
+    digest.finalize()
+}
+
 /// Compute next addresses
 fn next_addresses(address_block: &mut Block, input_block: &mut Block, zero_block: &Block) {
     input_block[6] += 1;
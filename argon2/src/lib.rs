@@ -0,0 +1,164 @@
+//! Argon2 password hashing function.
+//!
+//! The core fill/finalize algorithm lives in [`instance`], driven over a
+//! pluggable working-memory backing ([`memory::MemoryBlocks`]) and the block
+//! compression function in [`block`].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "parallel")]
+extern crate alloc;
+
+use blake2::digest;
+
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+mod block;
+mod instance;
+mod memory;
+
+#[cfg(feature = "kat")]
+pub use instance::Argon2Trace;
+#[cfg(feature = "kat")]
+pub use instance::MemoryView;
+
+pub(crate) use block::{Block, BLOCK_SIZE};
+pub(crate) use instance::initial_hash;
+pub(crate) use memory::{MemoryBlocks, SYNC_POINTS};
+
+/// Minimum digest output length, in bytes.
+pub(crate) const MIN_OUTLEN: u32 = 4;
+
+/// Maximum digest output length, in bytes.
+pub(crate) const MAX_OUTLEN: u32 = u32::MAX;
+
+/// Errors produced by this crate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// Output digest length falls outside `MIN_OUTLEN..=MAX_OUTLEN`.
+    OutputTooLong,
+}
+
+/// Argon2 variant to run.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Algorithm {
+    /// Data-depending memory access, maximally resistant to GPU cracking.
+    Argon2d = 0,
+    /// Data-independent memory access, resistant to side-channel attacks.
+    Argon2i = 1,
+    /// Hybrid of [`Algorithm::Argon2d`] and [`Algorithm::Argon2i`].
+    Argon2id = 2,
+}
+
+/// Argon2 version.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Version {
+    /// Version 0x10 (1.2.1 and earlier): overwrites instead of XORing.
+    V0x10 = 0x10,
+    /// Version 0x13 (1.3 and later): XORs new blocks over the old contents.
+    V0x13 = 0x13,
+}
+
+/// Argon2 context: algorithm, version, cost parameters, and the optional
+/// keyed-hashing inputs folded into `H0`.
+pub struct Argon2<'key> {
+    /// Argon2 variant.
+    pub(crate) alg: Algorithm,
+
+    /// Argon2 version.
+    pub(crate) version: Version,
+
+    /// Memory cost, in KiB.
+    pub(crate) m_cost: u32,
+
+    /// Number of passes.
+    pub(crate) t_cost: u32,
+
+    /// Degree of parallelism (lanes).
+    pub(crate) lanes: u32,
+
+    /// Number of threads to fill with.
+    pub(crate) threads: u32,
+
+    /// Secret key `K` for keyed hashing ("peppering"), folded into `H0`.
+    ///
+    /// Held as a mutable borrow (rather than `&[u8]`) so [`Self::initial_hash`]
+    /// can wipe the caller's buffer once it's been folded in, under the
+    /// `zeroize` feature.
+    pub(crate) secret: Option<&'key mut [u8]>,
+
+    /// Associated data `X`, folded into `H0`.
+    pub(crate) ad: Option<&'key [u8]>,
+}
+
+impl<'key> Argon2<'key> {
+    /// Create a new Argon2 context.
+    pub fn new(
+        alg: Algorithm,
+        version: Version,
+        m_cost: u32,
+        t_cost: u32,
+        lanes: u32,
+        threads: u32,
+    ) -> Self {
+        Self {
+            alg,
+            version,
+            m_cost,
+            t_cost,
+            lanes,
+            threads,
+            secret: None,
+            ad: None,
+        }
+    }
+
+    /// Set the secret key `K` used for keyed hashing ("peppering").
+    ///
+    /// `K` lives alongside the stored hash's salt but is kept separately
+    /// (e.g. in an HSM or an environment variable), and is folded into `H0`
+    /// by [`initial_hash`] rather than stored anywhere by this struct. Taken
+    /// as `&mut [u8]`, not `&[u8]`, so [`Self::initial_hash`] can zeroize the
+    /// caller's buffer once it's been consumed.
+    pub fn with_secret(mut self, secret: &'key mut [u8]) -> Self {
+        self.secret = Some(secret);
+        self
+    }
+
+    /// Set the associated data `X` folded into `H0`.
+    pub fn with_ad(mut self, ad: &'key [u8]) -> Self {
+        self.ad = Some(ad);
+        self
+    }
+
+    /// Compute `H0`, folding in this context's cost parameters plus the
+    /// optional `secret`/`ad`, per [`initial_hash`], then zeroize `secret`
+    /// (if set) now that it's been consumed.
+    pub(crate) fn initial_hash(
+        &mut self,
+        pwd: &[u8],
+        salt: &[u8],
+        out_len: u32,
+    ) -> digest::Output<blake2::Blake2b> {
+        let h0 = initial_hash(
+            self.lanes,
+            out_len,
+            self.m_cost,
+            self.t_cost,
+            self.version,
+            self.alg,
+            pwd,
+            salt,
+            self.secret.as_deref(),
+            self.ad,
+        );
+
+        #[cfg(feature = "zeroize")]
+        if let Some(secret) = self.secret.as_deref_mut() {
+            secret.zeroize();
+        }
+
+        h0
+    }
+}
@@ -2,9 +2,47 @@
 
 use crate::Block;
 
+#[cfg(feature = "parallel")]
+use alloc::vec::Vec;
+
 /// Number of synchronization points between lanes per pass
 pub(crate) const SYNC_POINTS: u32 = 4;
 
+/// Pluggable backing storage for Argon2's working memory blocks.
+///
+/// The fill/finalize algorithms only ever go through this trait, so a
+/// caller can hand `Instance::hash` any implementation in place of the
+/// default slice-backed [`Memory`] — for example a `memmap2`-backed region
+/// so the OS can page a large `m_cost`'s working set, an arena reused
+/// across repeated hashes in a server, or a buffer with its own
+/// zeroize-on-drop guard.
+pub(crate) trait MemoryBlocks {
+    /// Number of blocks.
+    fn len(&self) -> usize;
+
+    /// Get a copy of the block at `idx`.
+    fn get_block(&self, idx: usize) -> Block;
+
+    /// Get a mutable reference to the block at `idx`.
+    fn get_block_mut(&mut self, idx: usize) -> &mut Block;
+
+    /// Size of a memory segment, in blocks.
+    fn segment_length(&self) -> u32;
+
+    /// Split this backing into one disjoint mutable sub-slice per lane,
+    /// each `lane_length` blocks long, plus a read-only pointer to the
+    /// whole region for reading blocks other lanes have already completed.
+    ///
+    /// Returns `None` if this backing can't be split this way, in which
+    /// case filling falls back to a single thread. Only the slice-backed
+    /// [`Memory`] overrides this today; any other backing whose lanes are
+    /// laid out contiguously can do the same.
+    #[cfg(feature = "parallel")]
+    fn split_lanes_mut(&mut self, _lane_length: u32) -> Option<(*const Block, Vec<&mut [Block]>)> {
+        None
+    }
+}
+
 /// Structure containing references to the memory blocks
 pub(crate) struct Memory<'a> {
     /// Memory blocks
@@ -35,26 +73,50 @@ impl<'a> Memory<'a> {
             segment_length,
         }
     }
+}
+
+impl<'a> MemoryBlocks for Memory<'a> {
+    fn len(&self) -> usize {
+        self.data.len()
+    }
 
-    /// Get a copy of the block
-    pub(crate) fn get_block(&self, idx: usize) -> Block {
+    fn get_block(&self, idx: usize) -> Block {
         self.data[idx]
     }
 
-    /// Get a mutable reference to the block
-    pub(crate) fn get_block_mut(&mut self, idx: usize) -> &mut Block {
+    fn get_block_mut(&mut self, idx: usize) -> &mut Block {
         &mut self.data[idx]
     }
 
-    /// Size of the memory
-    #[inline]
-    pub(crate) fn len(&self) -> usize {
-        self.data.len()
+    fn segment_length(&self) -> u32 {
+        self.segment_length
     }
 
-    /// Size of a memory segment
-    #[inline]
-    pub(crate) fn segment_length(&self) -> u32 {
-        self.segment_length
+    #[cfg(feature = "parallel")]
+    fn split_lanes_mut(&mut self, lane_length: u32) -> Option<(*const Block, Vec<&mut [Block]>)> {
+        let lane_length = lane_length as usize;
+        let lanes = self.data.len() / lane_length;
+
+        // Safety: every slice below — the read-only `all_blocks` pointer
+        // and each lane's exclusive `&mut [Block]` — is carved out of the
+        // *same* raw pointer obtained from a single `as_mut_ptr()` call,
+        // rather than mixing that raw pointer with a later safe `&mut`
+        // reborrow of `self.data` (e.g. via `chunks_mut`), which would
+        // invalidate it. Lanes being laid out contiguously in `data` is
+        // what makes the `lane_length`-sized chunks built from `ptr`
+        // disjoint from one another.
+        #[allow(unsafe_code)]
+        {
+            let ptr = self.data.as_mut_ptr();
+            let all_blocks: *const Block = ptr;
+
+            let own_slices = (0..lanes)
+                .map(|l| unsafe {
+                    core::slice::from_raw_parts_mut(ptr.add(l * lane_length), lane_length)
+                })
+                .collect();
+
+            Some((all_blocks, own_slices))
+        }
     }
 }